@@ -0,0 +1,493 @@
+//! A minimal LSP client so the agent can ask a real language server for
+//! definitions, references, hover info, and diagnostics instead of
+//! grep-based guessing.
+//!
+//! Messages are JSON-RPC 2.0 framed with a `Content-Length` header, per the
+//! [Language Server Protocol](https://microsoft.github.io/language-server-protocol/).
+//! One background task drains the server's stdout, resolving pending
+//! requests by id and caching `textDocument/publishDiagnostics`
+//! notifications per URI.
+
+use crate::tools::ToolError;
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::ChildStdout;
+use tokio::sync::oneshot;
+use tokio::time::Duration;
+
+const LSP_SERVER_COMMAND: &str = "rust-analyzer";
+const LSP_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+type PendingMap = std::sync::Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+type DiagnosticsMap = std::sync::Arc<Mutex<HashMap<String, Value>>>;
+
+/// The version and text last pushed to the language server for an open
+/// document, so [`LspClient::ensure_open`] can tell whether the on-disk
+/// content has moved on since and needs a `didChange` to re-sync it.
+#[derive(Clone)]
+struct OpenDoc {
+    version: i64,
+    text: String,
+}
+
+struct LspClient {
+    stdin: tokio::sync::Mutex<tokio::process::ChildStdin>,
+    next_id: AtomicU64,
+    pending: PendingMap,
+    diagnostics: DiagnosticsMap,
+    opened: Mutex<HashMap<String, OpenDoc>>,
+}
+
+async fn write_message(
+    stdin: &mut tokio::process::ChildStdin,
+    message: &Value,
+) -> Result<(), ToolError> {
+    let body = serde_json::to_string(message)
+        .map_err(|e| ToolError(format!("Failed to encode LSP message: {}", e)))?;
+    let framed = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    stdin
+        .write_all(framed.as_bytes())
+        .await
+        .map_err(|e| ToolError(format!("Failed to write to language server: {}", e)))?;
+    stdin
+        .flush()
+        .await
+        .map_err(|e| ToolError(format!("Failed to write to language server: {}", e)))
+}
+
+/// Reads one `Content-Length`-framed JSON-RPC message. Returns `Ok(None)` on
+/// EOF, and also when a header block has no `Content-Length` or the body is
+/// not valid JSON.
+async fn read_message(reader: &mut BufReader<ChildStdout>) -> std::io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(len) = content_length else {
+        return Ok(None);
+    };
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+    Ok(serde_json::from_slice(&body).ok())
+}
+
+async fn read_loop(stdout: ChildStdout, pending: PendingMap, diagnostics: DiagnosticsMap) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let message = match read_message(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) | Err(_) => break,
+        };
+
+        if message.get("method").is_none() {
+            if let Some(id) = message.get("id").and_then(Value::as_u64)
+                && let Some(tx) = pending.lock().unwrap().remove(&id)
+            {
+                let result = message
+                    .get("result")
+                    .or_else(|| message.get("error"))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                tx.send(result).ok();
+            }
+            continue;
+        }
+
+        if message.get("method").and_then(Value::as_str) == Some("textDocument/publishDiagnostics")
+            && let Some(params) = message.get("params")
+            && let Some(uri) = params.get("uri").and_then(Value::as_str)
+        {
+            diagnostics
+                .lock()
+                .unwrap()
+                .insert(uri.to_string(), params.clone());
+        }
+    }
+
+    // The server's stdout hit EOF, errored, or sent a frame that couldn't be
+    // parsed: reading stops here either way (the server crashed, or we're
+    // shutting down, or the stream can no longer be trusted). Drop every outstanding sender so any `request()` still
+    // waiting on `rx.await` wakes immediately with "Language server closed
+    // before responding" instead of hanging forever.
+    pending.lock().unwrap().clear();
+}
+
+impl LspClient {
+    async fn spawn(command: &str, root_uri: &str) -> Result<std::sync::Arc<Self>, ToolError> {
+        use tokio::process::Command;
+
+        let mut child = Command::new(command)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .map_err(|e| ToolError(format!("Failed to spawn language server '{}': {}", command, e)))?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .expect("language server stdin is piped");
+        let stdout = child
+            .stdout
+            .take()
+            .expect("language server stdout is piped");
+
+        let pending: PendingMap = std::sync::Arc::new(Mutex::new(HashMap::new()));
+        let diagnostics: DiagnosticsMap = std::sync::Arc::new(Mutex::new(HashMap::new()));
+
+        let client = std::sync::Arc::new(LspClient {
+            stdin: tokio::sync::Mutex::new(stdin),
+            next_id: AtomicU64::new(1),
+            pending: pending.clone(),
+            diagnostics: diagnostics.clone(),
+            opened: Mutex::new(HashMap::new()),
+        });
+
+        tokio::spawn(read_loop(stdout, pending, diagnostics));
+        // `Child` isn't retained past this point: tokio doesn't kill the
+        // process on drop, and `read_loop` exiting (on stdout EOF) is how we
+        // notice the server went away.
+        drop(child);
+
+        client
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "rootUri": root_uri,
+                    "capabilities": {},
+                }),
+            )
+            .await?;
+        client.notify("initialized", json!({})).await?;
+
+        Ok(client)
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, ToolError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        {
+            let mut stdin = self.stdin.lock().await;
+            write_message(&mut stdin, &message).await?;
+        }
+
+        match tokio::time::timeout(Duration::from_secs(LSP_REQUEST_TIMEOUT_SECS), rx).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err(ToolError(
+                "Language server closed before responding".to_string(),
+            )),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(ToolError(format!(
+                    "Language server did not respond to '{}' within {}s",
+                    method, LSP_REQUEST_TIMEOUT_SECS
+                )))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), ToolError> {
+        let message = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        let mut stdin = self.stdin.lock().await;
+        write_message(&mut stdin, &message).await
+    }
+
+    /// Sends `textDocument/didOpen` for `path` the first time it's seen, or
+    /// `textDocument/didChange` if the on-disk content has moved on since
+    /// the last time it was pushed (e.g. after `write_file` edited it), so
+    /// `rust-analyzer` never serves navigation/diagnostics off a stale
+    /// buffer. Returns the `file://` URI used to address it in later
+    /// requests.
+    async fn ensure_open(&self, path: &str) -> Result<String, ToolError> {
+        let canonical = std::fs::canonicalize(path)
+            .map_err(|e| ToolError(format!("Failed to resolve path '{}': {}", path, e)))?;
+        let uri = format!("file://{}", canonical.display());
+        let text = std::fs::read_to_string(&canonical)
+            .map_err(|e| ToolError(format!("Failed to read file '{}': {}", path, e)))?;
+
+        let previous = self.opened.lock().unwrap().get(&uri).cloned();
+        match previous {
+            None => {
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": "rust",
+                            "version": 1,
+                            "text": text,
+                        }
+                    }),
+                )
+                .await?;
+                self.opened
+                    .lock()
+                    .unwrap()
+                    .insert(uri.clone(), OpenDoc { version: 1, text });
+            }
+            Some(doc) if doc.text != text => {
+                let version = doc.version + 1;
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": {"uri": uri, "version": version},
+                        "contentChanges": [{"text": text}],
+                    }),
+                )
+                .await?;
+                self.opened
+                    .lock()
+                    .unwrap()
+                    .insert(uri.clone(), OpenDoc { version, text });
+            }
+            Some(_) => {}
+        }
+
+        Ok(uri)
+    }
+}
+
+/// Lazily spawns and shares a single language server process across tools.
+#[derive(Clone, Default)]
+pub struct LspRegistry(std::sync::Arc<tokio::sync::Mutex<Option<std::sync::Arc<LspClient>>>>);
+
+impl LspRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn client(&self) -> Result<std::sync::Arc<LspClient>, ToolError> {
+        let mut guard = self.0.lock().await;
+        if let Some(client) = guard.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let root = std::env::current_dir()
+            .map_err(|e| ToolError(format!("Failed to get current directory: {}", e)))?;
+        let root_uri = format!("file://{}", root.display());
+        let client = LspClient::spawn(LSP_SERVER_COMMAND, &root_uri).await?;
+        *guard = Some(client.clone());
+        Ok(client)
+    }
+}
+
+fn position_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "path": {
+                "type": "string",
+                "description": "Path to the source file"
+            },
+            "line": {
+                "type": "integer",
+                "description": "Zero-based line number"
+            },
+            "character": {
+                "type": "integer",
+                "description": "Zero-based character offset within the line"
+            }
+        },
+        "required": ["path", "line", "character"]
+    })
+}
+
+#[derive(Deserialize)]
+pub struct LspPositionArgs {
+    path: String,
+    line: u32,
+    character: u32,
+}
+
+fn format_result(result: Value) -> String {
+    serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string())
+}
+
+pub struct LspDefinition {
+    registry: LspRegistry,
+}
+
+impl LspDefinition {
+    pub fn new(registry: LspRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for LspDefinition {
+    const NAME: &'static str = "lsp_definition";
+    type Error = ToolError;
+    type Args = LspPositionArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "lsp_definition".to_string(),
+            description: "Jump to the definition of the symbol at a file position, using the language server.".to_string(),
+            parameters: position_schema(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let client = self.registry.client().await?;
+        let uri = client.ensure_open(&args.path).await?;
+        let result = client
+            .request(
+                "textDocument/definition",
+                json!({
+                    "textDocument": {"uri": uri},
+                    "position": {"line": args.line, "character": args.character},
+                }),
+            )
+            .await?;
+        Ok(format_result(result))
+    }
+}
+
+pub struct LspReferences {
+    registry: LspRegistry,
+}
+
+impl LspReferences {
+    pub fn new(registry: LspRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for LspReferences {
+    const NAME: &'static str = "lsp_references";
+    type Error = ToolError;
+    type Args = LspPositionArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "lsp_references".to_string(),
+            description: "Find all references to the symbol at a file position, using the language server.".to_string(),
+            parameters: position_schema(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let client = self.registry.client().await?;
+        let uri = client.ensure_open(&args.path).await?;
+        let result = client
+            .request(
+                "textDocument/references",
+                json!({
+                    "textDocument": {"uri": uri},
+                    "position": {"line": args.line, "character": args.character},
+                    "context": {"includeDeclaration": true},
+                }),
+            )
+            .await?;
+        Ok(format_result(result))
+    }
+}
+
+pub struct LspHover {
+    registry: LspRegistry,
+}
+
+impl LspHover {
+    pub fn new(registry: LspRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for LspHover {
+    const NAME: &'static str = "lsp_hover";
+    type Error = ToolError;
+    type Args = LspPositionArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "lsp_hover".to_string(),
+            description: "Show type/doc info for the symbol at a file position, using the language server.".to_string(),
+            parameters: position_schema(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let client = self.registry.client().await?;
+        let uri = client.ensure_open(&args.path).await?;
+        let result = client
+            .request(
+                "textDocument/hover",
+                json!({
+                    "textDocument": {"uri": uri},
+                    "position": {"line": args.line, "character": args.character},
+                }),
+            )
+            .await?;
+        Ok(format_result(result))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LspDiagnosticsArgs {
+    path: String,
+}
+
+pub struct LspDiagnostics {
+    registry: LspRegistry,
+}
+
+impl LspDiagnostics {
+    pub fn new(registry: LspRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for LspDiagnostics {
+    const NAME: &'static str = "lsp_diagnostics";
+    type Error = ToolError;
+    type Args = LspDiagnosticsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "lsp_diagnostics".to_string(),
+            description: "Return the most recent diagnostics (errors/warnings) the language server has published for a file.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the source file"
+                    }
+                },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let client = self.registry.client().await?;
+        let uri = client.ensure_open(&args.path).await?;
+        match client.diagnostics.lock().unwrap().get(&uri) {
+            Some(params) => Ok(format_result(params.clone())),
+            None => Ok("No diagnostics received yet for this file".to_string()),
+        }
+    }
+}
@@ -2,234 +2,24 @@ use anyhow::Result;
 use futures::StreamExt;
 use rig::{
     agent::MultiTurnStreamItem,
-    completion::ToolDefinition,
     message::Message,
     prelude::*,
     providers::anthropic::{self, Client},
     streaming::{StreamedAssistantContent, StreamingPrompt},
-    tool::Tool,
 };
-use serde::{Deserialize, Serialize};
-use serde_json::json;
 use std::io::{self, Write};
-use tokio::time::{Duration, timeout};
 
-#[derive(Debug, thiserror::Error)]
-#[error("{0}")]
-struct ToolError(String);
+mod lsp;
+mod metrics;
+mod process;
+mod tools;
 
-#[derive(Deserialize)]
-struct ReadFileArgs {
-    path: String,
-}
-
-#[derive(Deserialize, Serialize)]
-struct ReadFile;
-
-impl Tool for ReadFile {
-    const NAME: &'static str = "read_file";
-    type Error = ToolError;
-    type Args = ReadFileArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: "read_file".to_string(),
-            description: "Read the contents of a file at the specified path. Returns the file contents as a string.".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "The path to the file to read"
-                    }
-                },
-                "required": ["path"]
-            }),
-        }
-    }
-
-    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        std::fs::read_to_string(&args.path)
-            .map_err(|e| ToolError(format!("Failed to read file '{}': {}", args.path, e)))
-    }
-}
-
-#[derive(Deserialize)]
-struct WriteFileArgs {
-    path: String,
-    content: String,
-}
-
-#[derive(Deserialize, Serialize)]
-struct WriteFile;
-
-impl Tool for WriteFile {
-    const NAME: &'static str = "write_file";
-    type Error = ToolError;
-    type Args = WriteFileArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: "write_file".to_string(),
-            description: "Write content to a file at the specified path. Creates parent directories if they don't exist. Overwrites the file if it already exists.".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "The path to the file to write"
-                    },
-                    "content": {
-                        "type": "string",
-                        "description": "The content to write to the file"
-                    }
-                },
-                "required": ["path", "content"]
-            }),
-        }
-    }
-
-    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        if let Some(parent) = std::path::Path::new(&args.path).parent() {
-            if !parent.as_os_str().is_empty() {
-                std::fs::create_dir_all(parent)
-                    .map_err(|e| ToolError(format!("Failed to create directories: {}", e)))?;
-            }
-        }
-
-        std::fs::write(&args.path, &args.content)
-            .map_err(|e| ToolError(format!("Failed to write file '{}': {}", args.path, e)))?;
-
-        Ok(format!(
-            "Successfully wrote {} bytes to '{}'",
-            args.content.len(),
-            args.path
-        ))
-    }
-}
-
-#[derive(Deserialize)]
-struct BashArgs {
-    command: String,
-}
-
-#[derive(Deserialize, Serialize)]
-struct Bash;
-
-const MAX_OUTPUT_BYTES: usize = 50 * 1024;
-const WARNING_TIMEOUT_SECS: u64 = 60;
-
-impl Tool for Bash {
-    const NAME: &'static str = "bash";
-    type Error = ToolError;
-    type Args = BashArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: "bash".to_string(),
-            description: "Execute a bash command and return its output. Use this for running shell commands, git operations, running tests, installing packages, etc. The command runs in the current working directory.".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "command": {
-                        "type": "string",
-                        "description": "The bash command to execute"
-                    }
-                },
-                "required": ["command"]
-            }),
-        }
-    }
-
-    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        use tokio::process::Command;
-
-        let mut child = Command::new("bash")
-            .arg("-c")
-            .arg(&args.command)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| ToolError(format!("Failed to spawn command: {}", e)))?;
-
-        let warning_duration = Duration::from_secs(WARNING_TIMEOUT_SECS);
-        let status = match timeout(warning_duration, child.wait()).await {
-            Ok(result) => result.map_err(|e| ToolError(format!("Command failed: {}", e)))?,
-            Err(_) => {
-                eprintln!(
-                    "\n[Command running for >{}s. Press Ctrl+C to interrupt]",
-                    WARNING_TIMEOUT_SECS
-                );
-                io::stderr().flush().ok();
-                child
-                    .wait()
-                    .await
-                    .map_err(|e| ToolError(format!("Command failed: {}", e)))?
-            }
-        };
-
-        let stdout = child.stdout.take();
-        let stderr = child.stderr.take();
-
-        let mut stdout_content = String::new();
-        let mut stderr_content = String::new();
-
-        if let Some(mut stdout) = stdout {
-            use tokio::io::AsyncReadExt;
-            let mut buf = Vec::new();
-            stdout.read_to_end(&mut buf).await.ok();
-            stdout_content = String::from_utf8_lossy(&buf).to_string();
-        }
-
-        if let Some(mut stderr) = stderr {
-            use tokio::io::AsyncReadExt;
-            let mut buf = Vec::new();
-            stderr.read_to_end(&mut buf).await.ok();
-            stderr_content = String::from_utf8_lossy(&buf).to_string();
-        }
-
-        let mut output = if status.success() {
-            let mut out = stdout_content;
-            if !stderr_content.is_empty() {
-                if !out.is_empty() {
-                    out.push('\n');
-                }
-                out.push_str("stderr:\n");
-                out.push_str(&stderr_content);
-            }
-            out
-        } else {
-            let mut out = format!("Exit code: {}\n", status.code().unwrap_or(-1));
-            if !stdout_content.is_empty() {
-                out.push_str("stdout:\n");
-                out.push_str(&stdout_content);
-                out.push('\n');
-            }
-            if !stderr_content.is_empty() {
-                out.push_str("stderr:\n");
-                out.push_str(&stderr_content);
-            }
-            out
-        };
-
-        let total_bytes = output.len();
-        if total_bytes > MAX_OUTPUT_BYTES {
-            output.truncate(MAX_OUTPUT_BYTES);
-            while !output.is_char_boundary(output.len()) {
-                output.pop();
-            }
-            output.push_str(&format!(
-                "\n... [output truncated, {} bytes total]",
-                total_bytes
-            ));
-        }
-
-        Ok(output)
-    }
-}
+use lsp::{LspDefinition, LspDiagnostics, LspHover, LspReferences, LspRegistry};
+use metrics::Metrics;
+use process::{
+    KillProcess, ProcessOutput, ProcessRegistry, ProcessStatus, StartProcess, WriteStdin,
+};
+use tools::{Bash, PtyBash, ReadFile, TailFile, WriteFile};
 
 const SYSTEM_PROMPT: &str = r#"You are Claude Code, an interactive AI coding assistant running in the terminal.
 
@@ -237,10 +27,25 @@ You have access to these tools:
 - bash: Execute shell commands (runs in current working directory)
 - read_file: Read file contents
 - write_file: Create or modify files
+- start_process: Launch a long-running background process (e.g. a dev server)
+- process_output: Read a background process's output since the last offset
+- write_stdin: Send a line to a background process's stdin
+- process_status: Check whether a background process is still running
+- kill_process: Terminate a background process
+- lsp_definition: Jump to a symbol's definition via the language server
+- lsp_references: Find references to a symbol via the language server
+- lsp_hover: Show type/doc info for a symbol via the language server
+- lsp_diagnostics: Read the latest errors/warnings for a file
+- tail_file: Show the end of a file, optionally following new output
+- pty_bash: Execute a bash command attached to a pseudo-terminal
 
 Guidelines:
 - Use bash to explore projects, run tests, git operations, etc.
 - Read files before modifying them to understand context
+- Use start_process for servers/watchers that should keep running across turns, not bash
+- Use tail_file to watch logs from a background process grow
+- Use pty_bash for interactive commands or ones that behave differently without a real tty (e.g. `git rebase -i`)
+- Prefer the lsp_* tools over grep when you need precise navigation (definitions, references, diagnostics)
 - Be concise and focused on solving the user's problem
 - When making changes, explain what you're doing briefly
 "#;
@@ -248,19 +53,48 @@ Guidelines:
 #[tokio::main]
 async fn main() -> Result<()> {
     let client = Client::from_env();
+    let process_registry = ProcessRegistry::new();
+    let lsp_registry = LspRegistry::new();
+    let metrics = Metrics::new();
 
     let agent = client
         .agent(anthropic::completion::CLAUDE_4_SONNET)
         .preamble(SYSTEM_PROMPT)
-        .tool(ReadFile)
-        .tool(WriteFile)
-        .tool(Bash)
+        .tool(ReadFile::new(metrics.clone()))
+        .tool(WriteFile::new(metrics.clone()))
+        .tool(Bash::new(metrics.clone()))
+        .tool(TailFile)
+        .tool(PtyBash)
+        .tool(StartProcess::new(process_registry.clone()))
+        .tool(ProcessOutput::new(process_registry.clone()))
+        .tool(WriteStdin::new(process_registry.clone()))
+        .tool(ProcessStatus::new(process_registry.clone()))
+        .tool(KillProcess::new(process_registry))
+        .tool(LspDefinition::new(lsp_registry.clone()))
+        .tool(LspReferences::new(lsp_registry.clone()))
+        .tool(LspHover::new(lsp_registry.clone()))
+        .tool(LspDiagnostics::new(lsp_registry))
         .max_tokens(8192)
         .build();
 
     println!("Rig Code v0.1.0");
     println!("Type 'exit' or 'quit' to exit.\n");
 
+    // `bash`/`pty_bash` each await `tokio::signal::ctrl_c()` while a command
+    // is running, but tokio installs that SIGINT handler globally and never
+    // uninstalls it, so nothing is listening for Ctrl+C while idle at the
+    // `> ` prompt. Keep a persistent listener that exits the process on an
+    // idle interrupt, leaving an in-flight command's own handler to consume
+    // the signal (tokio fans a single SIGINT out to every active awaiter).
+    tokio::spawn(async move {
+        loop {
+            if tokio::signal::ctrl_c().await.is_ok() && !tools::is_command_running() {
+                println!("\nGoodbye!");
+                std::process::exit(0);
+            }
+        }
+    });
+
     let stdin = io::stdin();
     let mut stdout = io::stdout();
     let mut history: Vec<Message> = Vec::new();
@@ -339,6 +173,9 @@ async fn main() -> Result<()> {
                     format_number(input_tokens),
                     format_number(output_tokens)
                 );
+                println!("{}", metrics.turn_summary());
+                #[cfg(feature = "metrics-sink")]
+                metrics.emit_to_sink();
                 println!();
 
                 history.push(Message::user(input));
@@ -0,0 +1,162 @@
+//! Per-tool execution metrics: call counts and the summed duration keyed
+//! by tool name, plus byte counters for file I/O. Only the total duration
+//! is kept per tool; no per-call distribution is recorded. Recorded through an RAII
+//! guard ([`ToolTimer`]) so timing is captured even on an early return or
+//! panic, not just the happy path.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+enum Outcome {
+    Failed,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Default, Clone, Copy)]
+struct ToolStats {
+    completed: u64,
+    failed: u64,
+    cancelled: u64,
+    total_duration: Duration,
+}
+
+#[derive(Default)]
+struct Inner {
+    by_tool: Mutex<HashMap<&'static str, ToolStats>>,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    /// Snapshot of `by_tool`'s bash stats and the byte counters as of the
+    /// end of the last `turn_summary` call, so that call can report just
+    /// this turn's delta instead of the cumulative session totals.
+    turn_snapshot: Mutex<TurnSnapshot>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct TurnSnapshot {
+    bash: ToolStats,
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+/// Cheaply cloneable handle to the process's tool metrics, shared by the
+/// tools that record into it (`read_file`, `write_file`, `bash`).
+#[derive(Clone, Default)]
+pub struct Metrics(std::sync::Arc<Inner>);
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a timer for `tool`. The result is recorded as `Failed` unless
+    /// the caller marks it completed or cancelled before it's dropped.
+    pub fn timer(&self, tool: &'static str) -> ToolTimer {
+        ToolTimer {
+            metrics: self.clone(),
+            tool,
+            start: Instant::now(),
+            outcome: Outcome::Failed,
+        }
+    }
+
+    pub fn record_bytes_read(&self, n: u64) {
+        self.0.bytes_read.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn record_bytes_written(&self, n: u64) {
+        self.0.bytes_written.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn record(&self, tool: &'static str, duration: Duration, outcome: Outcome) {
+        let mut by_tool = self.0.by_tool.lock().unwrap();
+        let stats = by_tool.entry(tool).or_default();
+        stats.total_duration += duration;
+        match outcome {
+            Outcome::Completed => stats.completed += 1,
+            Outcome::Failed => stats.failed += 1,
+            Outcome::Cancelled => stats.cancelled += 1,
+        }
+    }
+
+    /// A compact one-line summary of this turn's tool activity, meant to sit
+    /// alongside the `[Tokens: ... in / ... out]` line. Diffs against the
+    /// snapshot taken at the end of the previous call (zero, the first
+    /// time) so repeated calls each report only their own turn, not the
+    /// cumulative session totals.
+    pub fn turn_summary(&self) -> String {
+        let bash = self
+            .0
+            .by_tool
+            .lock()
+            .unwrap()
+            .get("bash")
+            .copied()
+            .unwrap_or_default();
+        let bytes_read = self.0.bytes_read.load(Ordering::Relaxed);
+        let bytes_written = self.0.bytes_written.load(Ordering::Relaxed);
+
+        let mut snapshot = self.0.turn_snapshot.lock().unwrap();
+        let prev = *snapshot;
+        *snapshot = TurnSnapshot {
+            bash,
+            bytes_read,
+            bytes_written,
+        };
+
+        let cmds = (bash.completed + bash.failed + bash.cancelled)
+            - (prev.bash.completed + prev.bash.failed + prev.bash.cancelled);
+        let duration = bash.total_duration - prev.bash.total_duration;
+
+        format!(
+            "[Bash: {} cmds, {:.1}s | Bytes: {} read / {} written]",
+            cmds,
+            duration.as_secs_f64(),
+            bytes_read - prev.bytes_read,
+            bytes_written - prev.bytes_written,
+        )
+    }
+
+    /// Emits the collected per-tool metrics as one `metric tool=...` line
+    /// per tool on stderr. Behind a feature flag since most runs have
+    /// nowhere to send them.
+    #[cfg(feature = "metrics-sink")]
+    pub fn emit_to_sink(&self) {
+        for (tool, stats) in self.0.by_tool.lock().unwrap().iter() {
+            eprintln!(
+                "metric tool={tool} completed={} failed={} cancelled={} total_duration={:?}",
+                stats.completed, stats.failed, stats.cancelled, stats.total_duration
+            );
+        }
+    }
+}
+
+/// Starts timing on construction and records the elapsed duration plus the
+/// current outcome (`Failed` unless marked otherwise) on [`Drop`], so
+/// panics and early returns in a tool's `call` still emit a measurement.
+pub struct ToolTimer {
+    metrics: Metrics,
+    tool: &'static str,
+    start: Instant,
+    outcome: Outcome,
+}
+
+impl ToolTimer {
+    pub fn mark_completed(&mut self) {
+        self.outcome = Outcome::Completed;
+    }
+
+    pub fn mark_cancelled(&mut self) {
+        self.outcome = Outcome::Cancelled;
+    }
+}
+
+impl Drop for ToolTimer {
+    fn drop(&mut self) {
+        self.metrics
+            .record(self.tool, self.start.elapsed(), self.outcome);
+    }
+}
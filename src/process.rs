@@ -0,0 +1,479 @@
+//! Long-lived background processes (dev servers, watchers, REPLs) that keep
+//! running across turns instead of blocking the one that launched them.
+//!
+//! [`ProcessRegistry`] is cloned into every process tool so they all see the
+//! same set of running children. Each [`ManagedProcess`] is driven by a
+//! background task that drains its stdout/stderr into a buffer capped at
+//! `MAX_BUFFER_BYTES`. Unlike `Bash`, which keeps the first bytes of its
+//! output and drops the rest, this buffer drops the oldest bytes first.
+
+use crate::tools::{ToolError, signal_process_group};
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::ChildStdin;
+use uuid::Uuid;
+
+const MAX_BUFFER_BYTES: usize = 100 * 1024;
+
+/// Combined stdout/stderr output for a managed process, capped at
+/// `MAX_BUFFER_BYTES` by dropping the oldest bytes first.
+///
+/// `window_start` is tracked explicitly (rather than derived as
+/// `total_bytes - data.len()`) because `data.len()` is not simply the sum of
+/// `chunk.len()` over pushed chunks: each chunk is lossily re-decoded as
+/// UTF-8 on its own, so a multi-byte character split across a read boundary
+/// can turn into multiple `U+FFFD` replacement characters and briefly make
+/// `data` longer than the bytes `total_bytes` has counted.
+#[derive(Default)]
+struct RingBuffer {
+    data: String,
+    total_bytes: usize,
+    window_start: usize,
+}
+
+impl RingBuffer {
+    fn push(&mut self, chunk: &[u8]) {
+        self.total_bytes += chunk.len();
+        self.data.push_str(&String::from_utf8_lossy(chunk));
+
+        if self.data.len() > MAX_BUFFER_BYTES {
+            let mut cut = self.data.len() - MAX_BUFFER_BYTES;
+            while !self.data.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.data.drain(..cut);
+            self.window_start += cut;
+        }
+    }
+
+    /// Returns the output produced since `offset` (a byte count into the
+    /// unbounded stream) along with the offset to pass next time.
+    fn since(&self, offset: usize) -> (&str, usize) {
+        let mut start = offset
+            .saturating_sub(self.window_start)
+            .min(self.data.len());
+        while !self.data.is_char_boundary(start) {
+            start += 1;
+        }
+        (&self.data[start..], self.total_bytes)
+    }
+}
+
+struct ManagedProcess {
+    pid: i32,
+    stdin: tokio::sync::Mutex<Option<ChildStdin>>,
+    output: Mutex<RingBuffer>,
+    status: Mutex<Option<std::process::ExitStatus>>,
+}
+
+/// Shared table of running/exited background processes, keyed by a
+/// generated id. Cheap to clone — clones share the same underlying table.
+#[derive(Clone, Default)]
+pub struct ProcessRegistry(Arc<Mutex<HashMap<String, Arc<ManagedProcess>>>>);
+
+impl ProcessRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get(&self, id: &str) -> Option<Arc<ManagedProcess>> {
+        self.0.lock().unwrap().get(id).cloned()
+    }
+}
+
+async fn spawn_managed(command: &str) -> Result<(String, Arc<ManagedProcess>), ToolError> {
+    use tokio::process::Command;
+
+    let mut child = Command::new("bash")
+        .arg("-c")
+        .arg(command)
+        .process_group(0)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| ToolError(format!("Failed to spawn process: {}", e)))?;
+
+    let pid = child
+        .id()
+        .ok_or_else(|| ToolError("Failed to get child pid".to_string()))? as i32;
+    let stdin = child.stdin.take();
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+
+    let process = Arc::new(ManagedProcess {
+        pid,
+        stdin: tokio::sync::Mutex::new(stdin),
+        output: Mutex::new(RingBuffer::default()),
+        status: Mutex::new(None),
+    });
+
+    let task_process = process.clone();
+    tokio::spawn(async move {
+        let mut stdout_buf = [0u8; 4096];
+        let mut stderr_buf = [0u8; 4096];
+        let mut stdout_eof = false;
+        let mut stderr_eof = false;
+        let mut status = None;
+
+        loop {
+            tokio::select! {
+                result = stdout.read(&mut stdout_buf), if !stdout_eof => {
+                    match result {
+                        Ok(0) | Err(_) => stdout_eof = true,
+                        Ok(n) => task_process.output.lock().unwrap().push(&stdout_buf[..n]),
+                    }
+                }
+                result = stderr.read(&mut stderr_buf), if !stderr_eof => {
+                    match result {
+                        Ok(0) | Err(_) => stderr_eof = true,
+                        Ok(n) => task_process.output.lock().unwrap().push(&stderr_buf[..n]),
+                    }
+                }
+                result = child.wait(), if status.is_none() => {
+                    status = result.ok();
+                }
+            }
+
+            if stdout_eof && stderr_eof && status.is_some() {
+                break;
+            }
+        }
+
+        *task_process.status.lock().unwrap() = status;
+    });
+
+    Ok((Uuid::new_v4().to_string(), process))
+}
+
+#[derive(Deserialize)]
+pub struct StartProcessArgs {
+    command: String,
+}
+
+pub struct StartProcess {
+    registry: ProcessRegistry,
+}
+
+impl StartProcess {
+    pub fn new(registry: ProcessRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for StartProcess {
+    const NAME: &'static str = "start_process";
+    type Error = ToolError;
+    type Args = StartProcessArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "start_process".to_string(),
+            description: "Start a long-running background process (e.g. a dev server or watcher) without blocking. Returns a process id that process_output, write_stdin, process_status, and kill_process take.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The command to run in the background"
+                    }
+                },
+                "required": ["command"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (id, process) = spawn_managed(&args.command).await?;
+        self.registry.0.lock().unwrap().insert(id.clone(), process);
+        Ok(format!("Started process '{}'", id))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProcessOutputArgs {
+    id: String,
+    #[serde(default)]
+    offset: usize,
+}
+
+pub struct ProcessOutput {
+    registry: ProcessRegistry,
+}
+
+impl ProcessOutput {
+    pub fn new(registry: ProcessRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for ProcessOutput {
+    const NAME: &'static str = "process_output";
+    type Error = ToolError;
+    type Args = ProcessOutputArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "process_output".to_string(),
+            description: "Read output produced by a background process since the given offset (0 for everything still buffered). Returns the new output plus the offset to pass on the next call.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The process id returned by start_process"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Byte offset to read from; omit or pass 0 to read from the start of what's still buffered"
+                    }
+                },
+                "required": ["id"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let process = self
+            .registry
+            .get(&args.id)
+            .ok_or_else(|| ToolError(format!("No such process '{}'", args.id)))?;
+
+        let (chunk, next_offset) = {
+            let buf = process.output.lock().unwrap();
+            let (chunk, next_offset) = buf.since(args.offset);
+            (chunk.to_string(), next_offset)
+        };
+        let running = process.status.lock().unwrap().is_none();
+
+        Ok(format!(
+            "{chunk}\n[offset: {next_offset}, status: {}]",
+            if running { "running" } else { "exited" }
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WriteStdinArgs {
+    id: String,
+    input: String,
+}
+
+pub struct WriteStdin {
+    registry: ProcessRegistry,
+}
+
+impl WriteStdin {
+    pub fn new(registry: ProcessRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for WriteStdin {
+    const NAME: &'static str = "write_stdin";
+    type Error = ToolError;
+    type Args = WriteStdinArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "write_stdin".to_string(),
+            description: "Write a line to a background process's stdin. A newline is appended automatically.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The process id returned by start_process"
+                    },
+                    "input": {
+                        "type": "string",
+                        "description": "The line to write to the process's stdin"
+                    }
+                },
+                "required": ["id", "input"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let process = self
+            .registry
+            .get(&args.id)
+            .ok_or_else(|| ToolError(format!("No such process '{}'", args.id)))?;
+
+        let mut stdin_guard = process.stdin.lock().await;
+        let stdin = stdin_guard
+            .as_mut()
+            .ok_or_else(|| ToolError(format!("Process '{}' has no stdin", args.id)))?;
+
+        stdin
+            .write_all(args.input.as_bytes())
+            .await
+            .map_err(|e| ToolError(format!("Failed to write to stdin: {}", e)))?;
+        stdin
+            .write_all(b"\n")
+            .await
+            .map_err(|e| ToolError(format!("Failed to write to stdin: {}", e)))?;
+
+        Ok(format!("Wrote {} bytes to '{}'", args.input.len(), args.id))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProcessStatusArgs {
+    id: String,
+}
+
+pub struct ProcessStatus {
+    registry: ProcessRegistry,
+}
+
+impl ProcessStatus {
+    pub fn new(registry: ProcessRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for ProcessStatus {
+    const NAME: &'static str = "process_status";
+    type Error = ToolError;
+    type Args = ProcessStatusArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "process_status".to_string(),
+            description: "Check whether a background process is still running, and its exit code if it has exited.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The process id returned by start_process"
+                    }
+                },
+                "required": ["id"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let process = self
+            .registry
+            .get(&args.id)
+            .ok_or_else(|| ToolError(format!("No such process '{}'", args.id)))?;
+
+        Ok(match *process.status.lock().unwrap() {
+            None => "running".to_string(),
+            Some(status) => format!("exited with code {}", status.code().unwrap_or(-1)),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct KillProcessArgs {
+    id: String,
+}
+
+pub struct KillProcess {
+    registry: ProcessRegistry,
+}
+
+impl KillProcess {
+    pub fn new(registry: ProcessRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl Tool for KillProcess {
+    const NAME: &'static str = "kill_process";
+    type Error = ToolError;
+    type Args = KillProcessArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "kill_process".to_string(),
+            description: "Terminate a background process (and its process group) by sending SIGTERM.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The process id returned by start_process"
+                    }
+                },
+                "required": ["id"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let process = self
+            .registry
+            .get(&args.id)
+            .ok_or_else(|| ToolError(format!("No such process '{}'", args.id)))?;
+
+        if process.status.lock().unwrap().is_some() {
+            return Ok(format!("Process '{}' has already exited", args.id));
+        }
+
+        signal_process_group(process.pid, libc::SIGTERM);
+        Ok(format!("Sent SIGTERM to process '{}'", args.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer_tracks_output_and_offset_without_eviction() {
+        let mut buf = RingBuffer::default();
+        buf.push(b"hello ");
+        buf.push(b"world");
+
+        let (chunk, next_offset) = buf.since(0);
+        assert_eq!(chunk, "hello world");
+        assert_eq!(next_offset, 11);
+
+        let (chunk, _) = buf.since(6);
+        assert_eq!(chunk, "world");
+    }
+
+    #[test]
+    fn ring_buffer_evicts_oldest_bytes_past_the_cap() {
+        let mut buf = RingBuffer::default();
+        buf.push(&[b'a'; MAX_BUFFER_BYTES]);
+        buf.push(b"bbbb");
+
+        assert_eq!(buf.data.len(), MAX_BUFFER_BYTES);
+        assert_eq!(buf.total_bytes, MAX_BUFFER_BYTES + 4);
+        // An offset from before the eviction clamps to the start of what's
+        // still buffered rather than underflowing.
+        let (chunk, next_offset) = buf.since(0);
+        assert_eq!(chunk.len(), MAX_BUFFER_BYTES);
+        assert!(chunk.ends_with("bbbb"));
+        assert_eq!(next_offset, MAX_BUFFER_BYTES + 4);
+    }
+
+    #[test]
+    fn ring_buffer_eviction_respects_utf8_char_boundaries() {
+        let mut buf = RingBuffer::default();
+        // Put a 2-byte character at the front, then pad so the next push's
+        // eviction cut point (byte 1) lands inside it if it weren't
+        // boundary-adjusted.
+        buf.push("é".as_bytes());
+        buf.push(&[b'a'; MAX_BUFFER_BYTES - 1]);
+
+        assert!(buf.data.is_char_boundary(0));
+        assert!(buf.data.len() <= MAX_BUFFER_BYTES);
+    }
+}
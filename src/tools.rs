@@ -1,21 +1,62 @@
+use crate::metrics::Metrics;
 use anyhow::Result;
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::io::{self, Write};
-use tokio::time::{Duration, timeout};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncReadExt, BufReader};
+use tokio::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
 #[error("{0}")]
-pub struct ToolError(String);
+pub struct ToolError(pub(crate) String);
+
+/// How many `Bash`, `PtyBash`, or `TailFile` calls are currently running
+/// (a `TailFile` call counts whether or not it follows). A count rather than a flag because several
+/// can legitimately be in flight at once within a single turn. `main`'s
+/// top-level idle Ctrl+C listener checks this so it doesn't treat a
+/// still-running command's own interrupt as a reason to exit the whole REPL.
+static COMMANDS_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// Reports whether any `bash`/`pty_bash`/`tail_file` command is currently
+/// in flight.
+pub fn is_command_running() -> bool {
+    COMMANDS_RUNNING.load(Ordering::SeqCst) > 0
+}
+
+/// Increments [`COMMANDS_RUNNING`] for the lifetime of the guard, decrementing
+/// it on drop so an early return or panic doesn't leave it stuck.
+struct RunningGuard;
+
+impl RunningGuard {
+    fn new() -> Self {
+        COMMANDS_RUNNING.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        COMMANDS_RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 #[derive(Deserialize)]
 pub struct ReadFileArgs {
     path: String,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ReadFile;
+pub struct ReadFile {
+    metrics: Metrics,
+}
+
+impl ReadFile {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
 
 impl Tool for ReadFile {
     const NAME: &'static str = "read_file";
@@ -41,12 +82,16 @@ impl Tool for ReadFile {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        std::fs::read_to_string(&args.path).map_err(|e| {
+        let mut timer = self.metrics.timer(Self::NAME);
+        let content = std::fs::read_to_string(&args.path).map_err(|e| {
             ToolError(format!(
                 "Failed to read file '{path}': {e}",
                 path = args.path
             ))
-        })
+        })?;
+        self.metrics.record_bytes_read(content.len() as u64);
+        timer.mark_completed();
+        Ok(content)
     }
 }
 
@@ -56,8 +101,15 @@ pub struct WriteFileArgs {
     content: String,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct WriteFile;
+pub struct WriteFile {
+    metrics: Metrics,
+}
+
+impl WriteFile {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
 
 impl Tool for WriteFile {
     const NAME: &'static str = "write_file";
@@ -87,6 +139,8 @@ impl Tool for WriteFile {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let mut timer = self.metrics.timer(Self::NAME);
+
         if let Some(parent) = std::path::Path::new(&args.path).parent()
             && !parent.as_os_str().is_empty()
         {
@@ -97,6 +151,10 @@ impl Tool for WriteFile {
         std::fs::write(&args.path, &args.content)
             .map_err(|e| ToolError(format!("Failed to write file '{}': {}", args.path, e)))?;
 
+        self.metrics
+            .record_bytes_written(args.content.len() as u64);
+        timer.mark_completed();
+
         Ok(format!(
             "Successfully wrote {} bytes to '{}'",
             args.content.len(),
@@ -110,99 +168,207 @@ pub struct BashArgs {
     command: String,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct Bash;
+pub struct Bash {
+    metrics: Metrics,
+}
+
+impl Bash {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+
+/// Appends `chunk` to `acc`, tracking the true byte count seen in `total_bytes`
+/// even after `acc` has been capped at `MAX_OUTPUT_BYTES`, so truncated runs
+/// still report how much output they actually produced.
+fn append_capped(acc: &mut String, total_bytes: &mut usize, chunk: &[u8]) {
+    *total_bytes += chunk.len();
+    if acc.len() >= MAX_OUTPUT_BYTES {
+        return;
+    }
+
+    let text = String::from_utf8_lossy(chunk);
+    let remaining = MAX_OUTPUT_BYTES - acc.len();
+    if text.len() <= remaining {
+        acc.push_str(&text);
+        return;
+    }
+
+    let mut cut = remaining;
+    while cut > 0 && !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    acc.push_str(&text[..cut]);
+}
+
+/// Sends `signal` to the process group led by `pid` so subprocesses spawned
+/// by the command (e.g. a shell pipeline) are terminated along with it.
+pub(crate) fn signal_process_group(pid: i32, signal: libc::c_int) {
+    unsafe {
+        libc::kill(-pid, signal);
+    }
+}
+
+/// The warning/Ctrl+C/kill-grace escalation shared by `Bash::run` and
+/// `PtyBash::call`: after [`WARNING_TIMEOUT_SECS`] print a one-time notice,
+/// on the first Ctrl+C SIGTERM the process group and arm a kill-grace
+/// timer, and on that timer's expiry SIGKILL it. Only the read source
+/// (pipe vs. pty fd) differs between the two callers, so that stays in
+/// their own `select!` loops while this holds the reaction to each event.
+struct Watchdog {
+    warned: bool,
+    interrupted: bool,
+    kill_grace_armed: bool,
+}
+
+impl Watchdog {
+    fn new() -> Self {
+        Self {
+            warned: false,
+            interrupted: false,
+            kill_grace_armed: false,
+        }
+    }
+
+    /// The "still running" timer fired.
+    fn on_warning(&mut self) {
+        self.warned = true;
+        eprintln!(
+            "\n[Command running for >{}s. Press Ctrl+C to interrupt]",
+            WARNING_TIMEOUT_SECS
+        );
+        io::stderr().flush().ok();
+    }
+
+    /// Ctrl+C fired for the first time: SIGTERM the process group and arm
+    /// the kill-grace timer.
+    fn on_ctrl_c(&mut self, pid: i32, kill_grace: Pin<&mut tokio::time::Sleep>) {
+        self.interrupted = true;
+        eprintln!("\n[Interrupting command (pid {pid})...]");
+        io::stderr().flush().ok();
+        signal_process_group(pid, libc::SIGTERM);
+        kill_grace.reset(tokio::time::Instant::now() + Duration::from_secs(KILL_GRACE_SECS));
+        self.kill_grace_armed = true;
+    }
+
+    /// The kill-grace timer fired: the SIGTERM didn't stop the process in
+    /// time, so escalate to SIGKILL.
+    fn on_kill_grace(&mut self, pid: i32) {
+        self.kill_grace_armed = false;
+        signal_process_group(pid, libc::SIGKILL);
+    }
+}
+
+/// The result of running a command: its captured output plus whether it
+/// was interrupted by the user, so callers don't have to sniff the output
+/// text to tell the two apart.
+struct BashOutput {
+    text: String,
+    interrupted: bool,
+}
 
 impl Bash {
-    async fn run(&self, args: BashArgs) -> Result<String, ToolError> {
+    async fn run(&self, args: BashArgs) -> Result<BashOutput, ToolError> {
         use tokio::process::Command;
 
+        let _running = RunningGuard::new();
+
         let mut child = Command::new("bash")
             .arg("-c")
             .arg(&args.command)
+            .process_group(0)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .spawn()
             .map_err(|e| ToolError(format!("Failed to spawn command: {}", e)))?;
 
-        let warning_duration = Duration::from_secs(WARNING_TIMEOUT_SECS);
-        let status = match timeout(warning_duration, child.wait()).await {
-            Ok(result) => result.map_err(|e| ToolError(format!("Command failed: {}", e)))?,
-            Err(_) => {
-                eprintln!(
-                    "\n[Command running for >{}s. Press Ctrl+C to interrupt]",
-                    WARNING_TIMEOUT_SECS
-                );
-                io::stderr().flush().ok();
-                child
-                    .wait()
-                    .await
-                    .map_err(|e| ToolError(format!("Command failed: {}", e)))?
-            }
-        };
+        let pid = child
+            .id()
+            .ok_or_else(|| ToolError("Failed to get child pid".to_string()))? as i32;
 
-        let stdout = child.stdout.take();
-        let stderr = child.stderr.take();
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
 
-        let mut stdout_content = String::new();
-        let mut stderr_content = String::new();
+        let mut stdout_buf = [0u8; 4096];
+        let mut stderr_buf = [0u8; 4096];
+        let mut stdout_eof = false;
+        let mut stderr_eof = false;
 
-        if let Some(mut stdout) = stdout {
-            use tokio::io::AsyncReadExt;
-            let mut buf = Vec::new();
-            stdout.read_to_end(&mut buf).await.ok();
-            stdout_content = String::from_utf8_lossy(&buf).to_string();
-        }
+        let mut captured = String::new();
+        let mut total_bytes = 0usize;
+        let mut status = None;
+        let mut watchdog = Watchdog::new();
+        let warning = tokio::time::sleep(Duration::from_secs(WARNING_TIMEOUT_SECS));
+        tokio::pin!(warning);
+        let kill_grace = tokio::time::sleep(Duration::from_secs(KILL_GRACE_SECS));
+        tokio::pin!(kill_grace);
 
-        if let Some(mut stderr) = stderr {
-            use tokio::io::AsyncReadExt;
-            let mut buf = Vec::new();
-            stderr.read_to_end(&mut buf).await.ok();
-            stderr_content = String::from_utf8_lossy(&buf).to_string();
-        }
-
-        let mut output = if status.success() {
-            let mut out = stdout_content;
-            if !stderr_content.is_empty() {
-                if !out.is_empty() {
-                    out.push('\n');
+        loop {
+            tokio::select! {
+                result = stdout.read(&mut stdout_buf), if !stdout_eof => {
+                    match result {
+                        Ok(0) | Err(_) => stdout_eof = true,
+                        Ok(n) => {
+                            io::stdout().write_all(&stdout_buf[..n]).ok();
+                            io::stdout().flush().ok();
+                            append_capped(&mut captured, &mut total_bytes, &stdout_buf[..n]);
+                        }
+                    }
+                }
+                result = stderr.read(&mut stderr_buf), if !stderr_eof => {
+                    match result {
+                        Ok(0) | Err(_) => stderr_eof = true,
+                        Ok(n) => {
+                            io::stderr().write_all(&stderr_buf[..n]).ok();
+                            io::stderr().flush().ok();
+                            append_capped(&mut captured, &mut total_bytes, &stderr_buf[..n]);
+                        }
+                    }
+                }
+                result = child.wait(), if status.is_none() => {
+                    status = Some(result.map_err(|e| ToolError(format!("Command failed: {}", e)))?);
+                }
+                _ = &mut warning, if !watchdog.warned => {
+                    watchdog.on_warning();
+                }
+                _ = tokio::signal::ctrl_c(), if !watchdog.interrupted => {
+                    watchdog.on_ctrl_c(pid, kill_grace.as_mut());
+                }
+                _ = &mut kill_grace, if watchdog.kill_grace_armed => {
+                    watchdog.on_kill_grace(pid);
                 }
-                out.push_str("stderr:\n");
-                out.push_str(&stderr_content);
-            }
-            out
-        } else {
-            let mut out = format!("Exit code: {}\n", status.code().unwrap_or(-1));
-            if !stdout_content.is_empty() {
-                out.push_str("stdout:\n");
-                out.push_str(&stdout_content);
-                out.push('\n');
             }
-            if !stderr_content.is_empty() {
-                out.push_str("stderr:\n");
-                out.push_str(&stderr_content);
+
+            if stdout_eof && stderr_eof && status.is_some() {
+                break;
             }
-            out
-        };
+        }
+
+        let status = status.expect("loop only exits once the child has exited");
+        let mut output = captured;
+        if watchdog.interrupted {
+            output = format!("[Command interrupted by user]\n{}", output);
+        } else if !status.success() {
+            output = format!("Exit code: {}\n{}", status.code().unwrap_or(-1), output);
+        }
 
-        let total_bytes = output.len();
         if total_bytes > MAX_OUTPUT_BYTES {
-            output.truncate(MAX_OUTPUT_BYTES);
-            while !output.is_char_boundary(output.len()) {
-                output.pop();
-            }
             output.push_str(&format!(
                 "\n... [output truncated, {} bytes total]",
                 total_bytes
             ));
         }
 
-        Ok(output)
+        Ok(BashOutput {
+            text: output,
+            interrupted: watchdog.interrupted,
+        })
     }
 }
 
 const MAX_OUTPUT_BYTES: usize = 50 * 1024;
 const WARNING_TIMEOUT_SECS: u64 = 60;
+const KILL_GRACE_SECS: u64 = 5;
 
 impl Tool for Bash {
     const NAME: &'static str = "bash";
@@ -228,6 +394,397 @@ impl Tool for Bash {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        self.run(args).await
+        let mut timer = self.metrics.timer(Self::NAME);
+        let result = self.run(args).await;
+        match &result {
+            Ok(output) if output.interrupted => timer.mark_cancelled(),
+            Ok(_) => timer.mark_completed(),
+            Err(_) => {}
+        }
+        result.map(|output| output.text)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TailFileArgs {
+    path: String,
+    #[serde(default)]
+    lines: Option<usize>,
+    #[serde(default)]
+    follow: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TailFile;
+
+const DEFAULT_TAIL_LINES: usize = 10;
+const TAIL_POLL_INTERVAL_MS: u64 = 500;
+const TAIL_IDLE_TIMEOUT_SECS: u64 = 10;
+
+impl Tool for TailFile {
+    const NAME: &'static str = "tail_file";
+    type Error = ToolError;
+    type Args = TailFileArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "tail_file".to_string(),
+            description: "Show the last lines of a file and, with follow=true, stream new lines as they're appended (e.g. a log from a background process) until the file goes idle or the command is interrupted.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The path to the file to tail"
+                    },
+                    "lines": {
+                        "type": "integer",
+                        "description": "Number of trailing lines to show initially (default 10)"
+                    },
+                    "follow": {
+                        "type": "boolean",
+                        "description": "Keep streaming new content as it's appended (default false)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let _running = RunningGuard::new();
+
+        let path = args.path;
+        let requested_lines = args.lines.unwrap_or(DEFAULT_TAIL_LINES);
+        let follow = args.follow.unwrap_or(false);
+
+        let initial = std::fs::read_to_string(&path)
+            .map_err(|e| ToolError(format!("Failed to read file '{}': {}", path, e)))?;
+        let mut backlog_lines: Vec<&str> = initial.lines().rev().take(requested_lines).collect();
+        backlog_lines.reverse();
+        let backlog = backlog_lines.join("\n");
+
+        let mut captured = String::new();
+        let mut total_bytes = 0usize;
+        if !backlog.is_empty() {
+            println!("{backlog}");
+            io::stdout().flush().ok();
+            append_capped(&mut captured, &mut total_bytes, backlog.as_bytes());
+            append_capped(&mut captured, &mut total_bytes, b"\n");
+        }
+
+        let mut offset = initial.len() as u64;
+
+        if follow {
+            let mut interval = tokio::time::interval(Duration::from_millis(TAIL_POLL_INTERVAL_MS));
+            let idle_timeout = Duration::from_secs(TAIL_IDLE_TIMEOUT_SECS);
+            let mut last_growth = tokio::time::Instant::now();
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let size = match std::fs::metadata(&path) {
+                            Ok(metadata) => metadata.len(),
+                            Err(_) => continue,
+                        };
+
+                        if size < offset {
+                            // The file was truncated or rotated; start over from the new beginning.
+                            offset = 0;
+                        }
+
+                        if size > offset {
+                            use std::io::{Read, Seek, SeekFrom};
+                            let mut file = std::fs::File::open(&path)
+                                .map_err(|e| ToolError(format!("Failed to open file '{}': {}", path, e)))?;
+                            file.seek(SeekFrom::Start(offset))
+                                .map_err(|e| ToolError(format!("Failed to seek file '{}': {}", path, e)))?;
+                            let mut chunk = Vec::new();
+                            file.read_to_end(&mut chunk)
+                                .map_err(|e| ToolError(format!("Failed to read file '{}': {}", path, e)))?;
+
+                            io::stdout().write_all(&chunk).ok();
+                            io::stdout().flush().ok();
+                            append_capped(&mut captured, &mut total_bytes, &chunk);
+
+                            offset = size;
+                            last_growth = tokio::time::Instant::now();
+                        } else if last_growth.elapsed() >= idle_timeout {
+                            break;
+                        }
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        eprintln!("\n[Stopped tailing '{}']", path);
+                        io::stderr().flush().ok();
+                        break;
+                    }
+                }
+            }
+        }
+
+        if total_bytes > MAX_OUTPUT_BYTES {
+            captured.push_str(&format!(
+                "\n... [output truncated, {} bytes total]",
+                total_bytes
+            ));
+        }
+
+        Ok(captured)
+    }
+}
+
+/// Owns the pty master fd so it's closed exactly once the command is done
+/// with it, and lets `AsyncFd` poll it for readiness.
+struct PtyMaster(std::os::fd::RawFd);
+
+impl std::os::fd::AsRawFd for PtyMaster {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.0
+    }
+}
+
+impl Drop for PtyMaster {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+fn open_pty(rows: u16, cols: u16) -> Result<(std::os::fd::RawFd, std::os::fd::RawFd), ToolError> {
+    let mut master: libc::c_int = -1;
+    let mut slave: libc::c_int = -1;
+    let winsize = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    let result = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &winsize as *const libc::winsize as *mut libc::winsize,
+        )
+    };
+    if result != 0 {
+        return Err(ToolError(format!(
+            "Failed to open pty: {}",
+            io::Error::last_os_error()
+        )));
+    }
+
+    let flags = unsafe { libc::fcntl(master, libc::F_GETFL) };
+    unsafe {
+        libc::fcntl(master, libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+
+    Ok((master, slave))
+}
+
+#[derive(Deserialize)]
+pub struct PtyBashArgs {
+    command: String,
+    #[serde(default)]
+    rows: Option<u16>,
+    #[serde(default)]
+    cols: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PtyBash;
+
+const DEFAULT_PTY_ROWS: u16 = 24;
+const DEFAULT_PTY_COLS: u16 = 80;
+
+impl Tool for PtyBash {
+    const NAME: &'static str = "pty_bash";
+    type Error = ToolError;
+    type Args = PtyBashArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "pty_bash".to_string(),
+            description: "Execute a bash command attached to a pseudo-terminal instead of plain pipes. Use this for interactive programs, `git rebase -i`, or anything that checks isatty() or relies on a tty for colored/progress output.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The bash command to execute"
+                    },
+                    "rows": {
+                        "type": "integer",
+                        "description": "Initial terminal height (default 24)"
+                    },
+                    "cols": {
+                        "type": "integer",
+                        "description": "Initial terminal width (default 80)"
+                    }
+                },
+                "required": ["command"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::io::FromRawFd;
+        use tokio::io::unix::AsyncFd;
+        use tokio::process::Command;
+
+        let _running = RunningGuard::new();
+
+        let rows = args.rows.unwrap_or(DEFAULT_PTY_ROWS);
+        let cols = args.cols.unwrap_or(DEFAULT_PTY_COLS);
+        let (master_fd, slave_fd) = open_pty(rows, cols)?;
+        let master = PtyMaster(master_fd);
+
+        let child = unsafe {
+            Command::new("bash")
+                .arg("-c")
+                .arg(&args.command)
+                .stdin(std::process::Stdio::from_raw_fd(libc::dup(slave_fd)))
+                .stdout(std::process::Stdio::from_raw_fd(libc::dup(slave_fd)))
+                .stderr(std::process::Stdio::from_raw_fd(libc::dup(slave_fd)))
+                .pre_exec(|| {
+                    // Make the pty our controlling terminal so programs that
+                    // check isatty()/tcgetattr() behave as they would in a
+                    // real shell.
+                    libc::setsid();
+                    libc::ioctl(0, libc::TIOCSCTTY as _, 0);
+                    Ok(())
+                })
+                .spawn()
+        }
+        .map_err(|e| ToolError(format!("Failed to spawn command: {}", e)));
+        unsafe {
+            libc::close(slave_fd);
+        }
+        let mut child = child?;
+
+        let pid = child
+            .id()
+            .ok_or_else(|| ToolError("Failed to get child pid".to_string()))? as i32;
+
+        let async_master =
+            AsyncFd::new(master).map_err(|e| ToolError(format!("Failed to watch pty master: {}", e)))?;
+
+        let mut captured = String::new();
+        let mut total_bytes = 0usize;
+        let mut status = None;
+        let mut watchdog = Watchdog::new();
+        let mut master_eof = false;
+        let warning = tokio::time::sleep(Duration::from_secs(WARNING_TIMEOUT_SECS));
+        tokio::pin!(warning);
+        let kill_grace = tokio::time::sleep(Duration::from_secs(KILL_GRACE_SECS));
+        tokio::pin!(kill_grace);
+
+        loop {
+            tokio::select! {
+                result = async_master.readable(), if !master_eof => {
+                    let mut guard = match result {
+                        Ok(guard) => guard,
+                        Err(_) => { master_eof = true; continue; }
+                    };
+                    let mut buf = [0u8; 4096];
+                    let read = guard.try_io(|inner| {
+                        let n = unsafe {
+                            libc::read(inner.get_ref().as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+                        };
+                        if n < 0 {
+                            Err(io::Error::last_os_error())
+                        } else {
+                            Ok(n as usize)
+                        }
+                    });
+                    match read {
+                        Ok(Ok(0)) => master_eof = true,
+                        Ok(Ok(n)) => {
+                            io::stdout().write_all(&buf[..n]).ok();
+                            io::stdout().flush().ok();
+                            append_capped(&mut captured, &mut total_bytes, &buf[..n]);
+                        }
+                        Ok(Err(e)) if e.raw_os_error() == Some(libc::EIO) => master_eof = true,
+                        Ok(Err(_)) => master_eof = true,
+                        Err(_would_block) => {}
+                    }
+                }
+                result = child.wait(), if status.is_none() => {
+                    status = Some(result.map_err(|e| ToolError(format!("Command failed: {}", e)))?);
+                }
+                _ = &mut warning, if !watchdog.warned => {
+                    watchdog.on_warning();
+                }
+                _ = tokio::signal::ctrl_c(), if !watchdog.interrupted => {
+                    watchdog.on_ctrl_c(pid, kill_grace.as_mut());
+                }
+                _ = &mut kill_grace, if watchdog.kill_grace_armed => {
+                    watchdog.on_kill_grace(pid);
+                }
+            }
+
+            if master_eof && status.is_some() {
+                break;
+            }
+        }
+
+        let status = status.expect("loop only exits once the child has exited");
+        let mut output = captured;
+        if watchdog.interrupted {
+            output = format!("[Command interrupted by user]\n{}", output);
+        } else if !status.success() {
+            output = format!("Exit code: {}\n{}", status.code().unwrap_or(-1), output);
+        }
+
+        if total_bytes > MAX_OUTPUT_BYTES {
+            output.push_str(&format!(
+                "\n... [output truncated, {} bytes total]",
+                total_bytes
+            ));
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_capped_joins_chunks_under_the_limit() {
+        let mut acc = String::new();
+        let mut total_bytes = 0;
+        append_capped(&mut acc, &mut total_bytes, b"hello ");
+        append_capped(&mut acc, &mut total_bytes, b"world");
+        assert_eq!(acc, "hello world");
+        assert_eq!(total_bytes, 11);
+    }
+
+    #[test]
+    fn append_capped_stops_writing_past_the_byte_cap_but_keeps_counting() {
+        let mut acc = "x".repeat(MAX_OUTPUT_BYTES);
+        let mut total_bytes = MAX_OUTPUT_BYTES;
+        append_capped(&mut acc, &mut total_bytes, b"overflow");
+        assert_eq!(acc.len(), MAX_OUTPUT_BYTES);
+        assert_eq!(total_bytes, MAX_OUTPUT_BYTES + 8);
+    }
+
+    #[test]
+    fn append_capped_truncates_at_a_char_boundary() {
+        let mut acc = "x".repeat(MAX_OUTPUT_BYTES - 1);
+        let mut total_bytes = acc.len();
+        // "é" is 2 bytes in UTF-8; only one byte of space remains, so the
+        // whole character must be dropped rather than split.
+        append_capped(&mut acc, &mut total_bytes, "é".as_bytes());
+        assert_eq!(acc.len(), MAX_OUTPUT_BYTES - 1);
+        assert!(acc.is_char_boundary(acc.len()));
+        assert_eq!(total_bytes, MAX_OUTPUT_BYTES - 1 + 2);
     }
 }